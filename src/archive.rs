@@ -0,0 +1,252 @@
+//! Append-only history, inspired by netidx-archive: instead of the live
+//! `files` table's `DELETE FROM files WHERE last_seen <> ?1` discarding
+//! whatever changed since the last scan, each scan is recorded as an
+//! immutable generation. `scans` has one row per run; `events` logs what
+//! changed in that run. Reconstructing the file set as of any past scan, or
+//! diffing any two scans, is just replaying `events` up to the generation(s)
+//! in question — nothing is ever overwritten or deleted.
+
+#![allow(dead_code)]
+
+use crate::Datei;
+use crate::timestamp::TruncatedTimestamp;
+use rusqlite::{Connection, Result, params};
+use std::collections::HashMap;
+
+pub fn create_archive_tables(connection: &Connection) -> Result<()> {
+    connection.execute_batch(
+        "
+        CREATE TABLE IF NOT EXISTS scans (
+            scan_id INTEGER PRIMARY KEY AUTOINCREMENT,
+            timestamp INTEGER NOT NULL,
+            root TEXT NOT NULL,
+            file_count INTEGER NOT NULL,
+            total_bytes INTEGER NOT NULL
+        );
+        CREATE TABLE IF NOT EXISTS events (
+            scan_id INTEGER NOT NULL REFERENCES scans(scan_id),
+            hash TEXT NOT NULL,
+            path TEXT NOT NULL,
+            kind TEXT NOT NULL,
+            size INTEGER NOT NULL,
+            created INTEGER NOT NULL,
+            modified INTEGER NOT NULL,
+            modified_nanos INTEGER NOT NULL,
+            plen INTEGER NOT NULL,
+            flen INTEGER NOT NULL
+        );
+        CREATE INDEX IF NOT EXISTS idx_events_scan_id ON events(scan_id);
+        CREATE INDEX IF NOT EXISTS idx_events_path ON events(path);
+        ",
+    )
+}
+
+#[derive(Clone, Debug)]
+pub struct ArchivedFile {
+    pub hash: String,
+    pub path: String,
+    pub size: i64,
+    pub created: i64,
+    pub modified: TruncatedTimestamp,
+    pub plen: i64,
+    pub flen: i64,
+}
+
+pub struct Diff {
+    pub new: Vec<ArchivedFile>,
+    pub modified: Vec<ArchivedFile>,
+    pub deleted: Vec<ArchivedFile>,
+}
+
+const KIND_ADDED: &str = "added";
+const KIND_MODIFIED: &str = "modified";
+const KIND_DELETED: &str = "deleted";
+
+fn latest_scan_id(connection: &Connection) -> Result<Option<i64>> {
+    connection
+        .query_row("SELECT MAX(scan_id) FROM scans", [], |row| row.get(0))
+}
+
+/// Replays every event with `scan_id <= as_of` in order, building the file
+/// set as it stood at that generation. `None` (no generation yet) yields an
+/// empty set.
+pub fn reconstruct_as_of(
+    connection: &Connection,
+    as_of: Option<i64>,
+) -> Result<HashMap<String, ArchivedFile>> {
+    let mut files = HashMap::new();
+    let as_of = match as_of {
+        Some(id) => id,
+        None => return Ok(files),
+    };
+
+    let mut stmt = connection.prepare(
+        "SELECT hash, path, kind, size, created, modified, modified_nanos, plen, flen
+         FROM events WHERE scan_id <= ?1 ORDER BY scan_id ASC",
+    )?;
+    let mut rows = stmt.query([as_of])?;
+
+    while let Some(row) = rows.next()? {
+        let kind: String = row.get(2)?;
+        let path: String = row.get(1)?;
+
+        if kind == KIND_DELETED {
+            files.remove(&path);
+            continue;
+        }
+
+        files.insert(
+            path.clone(),
+            ArchivedFile {
+                hash: row.get(0)?,
+                path,
+                size: row.get(3)?,
+                created: row.get(4)?,
+                modified: TruncatedTimestamp {
+                    seconds: row.get(5)?,
+                    nanos: row.get(6)?,
+                    second_ambiguous: false,
+                },
+                plen: row.get(7)?,
+                flen: row.get(8)?,
+            },
+        );
+    }
+
+    Ok(files)
+}
+
+/// Diffs the file sets at two generations, e.g. "what changed between
+/// Tuesday and Friday" answered long after both scans ran.
+pub fn diff_between(connection: &Connection, from: Option<i64>, to: i64) -> Result<Diff> {
+    let before = reconstruct_as_of(connection, from)?;
+    let after = reconstruct_as_of(connection, Some(to))?;
+
+    let mut new = Vec::new();
+    let mut modified = Vec::new();
+    for (path, file) in &after {
+        match before.get(path) {
+            None => new.push(file.clone()),
+            Some(prior) => {
+                if prior.size != file.size || prior.modified.maybe_changed(&file.modified) {
+                    modified.push(file.clone());
+                }
+            }
+        }
+    }
+
+    let deleted = before
+        .into_iter()
+        .filter(|(path, _)| !after.contains_key(path))
+        .map(|(_, file)| file)
+        .collect();
+
+    Ok(Diff {
+        new,
+        modified,
+        deleted,
+    })
+}
+
+/// Records `entries` as a new generation: diffs them against the file set
+/// reconstructed from the previous scan, appends one event per added,
+/// modified or deleted path, and returns that diff (the same shape the
+/// SQLite and snapshot backends report after each run).
+pub fn record_scan(
+    connection: &mut Connection,
+    root: &str,
+    entries: &[Datei],
+    timestamp: i64,
+) -> Result<Diff> {
+    let prior_scan_id = latest_scan_id(connection)?;
+    let prior = reconstruct_as_of(connection, prior_scan_id)?;
+
+    let total_bytes: i64 = entries.iter().map(|e| e.size).sum();
+
+    let tx = connection.transaction()?;
+    tx.execute(
+        "INSERT INTO scans (timestamp, root, file_count, total_bytes) VALUES (?1, ?2, ?3, ?4)",
+        params![timestamp, root, entries.len() as i64, total_bytes],
+    )?;
+    let scan_id = tx.last_insert_rowid();
+
+    let mut insert_event = tx.prepare(
+        "INSERT INTO events (scan_id, hash, path, kind, size, created, modified, modified_nanos, plen, flen)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+    )?;
+
+    let mut new = Vec::new();
+    let mut modified = Vec::new();
+    let mut seen_paths = std::collections::HashSet::new();
+
+    for entry in entries {
+        seen_paths.insert(entry.path.clone());
+
+        let kind = match prior.get(&entry.path) {
+            None => KIND_ADDED,
+            Some(prior_file)
+                if prior_file.size != entry.size
+                    || prior_file.modified.maybe_changed(&entry.modified) =>
+            {
+                KIND_MODIFIED
+            }
+            Some(_) => continue,
+        };
+
+        insert_event.execute(params![
+            scan_id,
+            entry.content_hash,
+            entry.path,
+            kind,
+            entry.size,
+            entry.created,
+            entry.modified.seconds,
+            entry.modified.nanos,
+            entry.plen,
+            entry.flen,
+        ])?;
+
+        let archived = ArchivedFile {
+            hash: entry.content_hash.clone(),
+            path: entry.path.clone(),
+            size: entry.size,
+            created: entry.created,
+            modified: entry.modified,
+            plen: entry.plen,
+            flen: entry.flen,
+        };
+        if kind == KIND_ADDED {
+            new.push(archived);
+        } else {
+            modified.push(archived);
+        }
+    }
+
+    let mut deleted = Vec::new();
+    for (path, file) in &prior {
+        if !seen_paths.contains(path) {
+            insert_event.execute(params![
+                scan_id,
+                file.hash,
+                file.path,
+                KIND_DELETED,
+                file.size,
+                file.created,
+                file.modified.seconds,
+                file.modified.nanos,
+                file.plen,
+                file.flen,
+            ])?;
+            deleted.push(file.clone());
+        }
+    }
+
+    drop(insert_event);
+    tx.commit()?;
+
+    Ok(Diff {
+        new,
+        modified,
+        deleted,
+    })
+}
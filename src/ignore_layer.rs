@@ -0,0 +1,135 @@
+//! Opt-in `.gitignore`-style filtering for the directory walk, modeled after
+//! ripgrep's `ignore` crate: each directory's own `.gitignore`/`.ignore`/
+//! `.fdignore` files are compiled and combined with the matchers inherited
+//! from its parents, so a child directory can both add and (via `!`
+//! negation) undo exclusions from its ancestors.
+
+use ignore::{
+    Match,
+    gitignore::{Gitignore, GitignoreBuilder},
+};
+use std::{
+    collections::HashMap,
+    fs::DirEntry,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+/// Names of ignore files honored in every directory, in the order ripgrep
+/// checks them.
+const IGNORE_FILE_NAMES: [&str; 3] = [".gitignore", ".ignore", ".fdignore"];
+
+#[derive(Clone, Default)]
+pub struct IgnoreConfig {
+    /// Extra glob patterns applied everywhere, as if listed in a `.gitignore`
+    /// at the root of the walk.
+    pub overrides: Vec<String>,
+    /// Also honor a global ignore file: the path in the `GIT_EXCLUDESFILE`
+    /// environment variable, else `$HOME/.config/git/ignore`. Git's own
+    /// `core.excludesFile` setting is not read.
+    pub global_ignore: bool,
+}
+
+/// A chain of compiled matchers from the walk root down to one directory.
+/// Deeper matchers are checked last, so their `!`-negations can re-include a
+/// path an ancestor excluded.
+type Chain = Vec<Arc<Gitignore>>;
+
+/// Shared across the directory walk: compiles and caches the ignore chain
+/// for every directory visited exactly once.
+pub struct IgnoreLayer {
+    root: PathBuf,
+    chains: Mutex<HashMap<PathBuf, Arc<Chain>>>,
+}
+
+impl IgnoreLayer {
+    pub fn new(root: &Path, config: &IgnoreConfig) -> Self {
+        let mut builder = GitignoreBuilder::new(root);
+        for pattern in &config.overrides {
+            let _ = builder.add_line(None, pattern);
+        }
+        if config.global_ignore
+            && let Some(global) = global_ignore_path()
+        {
+            let _ = builder.add(global);
+        }
+        let base = builder.build().unwrap_or_else(|_| Gitignore::empty());
+
+        let mut chains = HashMap::new();
+        chains.insert(root.to_path_buf(), Arc::new(vec![Arc::new(base)]));
+
+        IgnoreLayer {
+            root: root.to_path_buf(),
+            chains: Mutex::new(chains),
+        }
+    }
+
+    /// Filters `children` of `dir` in place, dropping entries matched by the
+    /// combined ignore chain, and records the chain for `dir` (extended with
+    /// any ignore files found in `dir` itself) so subdirectories inherit it.
+    pub fn filter_children(&self, dir: &Path, children: &mut Vec<DirEntry>) {
+        let parent_chain = self.chain_for(dir);
+
+        let mut local_builder = GitignoreBuilder::new(dir);
+        let mut has_local = false;
+        for name in IGNORE_FILE_NAMES {
+            let candidate = dir.join(name);
+            if candidate.is_file() {
+                let _ = local_builder.add(candidate);
+                has_local = true;
+            }
+        }
+
+        let full_chain = if has_local {
+            if let Ok(local) = local_builder.build() {
+                let mut chain = (*parent_chain).clone();
+                chain.push(Arc::new(local));
+                Arc::new(chain)
+            } else {
+                parent_chain.clone()
+            }
+        } else {
+            parent_chain.clone()
+        };
+
+        self.chains
+            .lock()
+            .unwrap()
+            .insert(dir.to_path_buf(), full_chain.clone());
+
+        children.retain(|entry| {
+            let is_dir = entry.file_type().map(|t| t.is_dir()).unwrap_or(false);
+            !self.is_ignored(&full_chain, &entry.path(), is_dir)
+        });
+    }
+
+    fn chain_for(&self, dir: &Path) -> Arc<Chain> {
+        if let Some(chain) = self.chains.lock().unwrap().get(dir) {
+            return chain.clone();
+        }
+        match dir.parent() {
+            Some(parent) if dir != self.root => self.chain_for(parent),
+            _ => Arc::new(Vec::new()),
+        }
+    }
+
+    fn is_ignored(&self, chain: &Chain, path: &Path, is_dir: bool) -> bool {
+        let mut ignored = false;
+        for matcher in chain {
+            match matcher.matched(path, is_dir) {
+                Match::None => {}
+                Match::Ignore(_) => ignored = true,
+                Match::Whitelist(_) => ignored = false,
+            }
+        }
+        ignored
+    }
+}
+
+fn global_ignore_path() -> Option<PathBuf> {
+    if let Ok(path) = std::env::var("GIT_EXCLUDESFILE") {
+        return Some(PathBuf::from(path));
+    }
+    let home = std::env::var("HOME").ok()?;
+    Some(PathBuf::from(home).join(".config/git/ignore"))
+}
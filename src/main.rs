@@ -1,22 +1,41 @@
+mod archive;
+mod direct_io;
+mod fs_source;
+mod ignore_layer;
+mod snapshot;
+mod timestamp;
+
 use chrono::{Local, TimeZone};
-use jwalk::{DirEntry, WalkDir};
+use ignore_layer::{IgnoreConfig, IgnoreLayer};
 use rusqlite::{Connection, Result, Row, Rows, params};
 use std::{
+    collections::HashMap,
     fs::File,
-    io::{BufWriter, Write},
+    io::{BufReader, BufWriter, Read, Write},
+    path::Path,
 };
 use tabled::{Table, builder::Builder, settings::Style};
-use xxhash_rust::xxh3::xxh3_64;
+use timestamp::TruncatedTimestamp;
+use xxhash_rust::xxh3::{Xxh3, xxh3_64};
 
 #[derive(Debug)]
-struct Datei {
+pub(crate) struct Datei {
     hash: String,
-    path: String,
-    size: i64,
-    created: i64,
-    modified: i64,
-    plen: i64,
-    flen: i64,
+    pub(crate) content_hash: String,
+    pub(crate) path: String,
+    pub(crate) size: i64,
+    pub(crate) created: i64,
+    pub(crate) modified: TruncatedTimestamp,
+    pub(crate) plen: i64,
+    pub(crate) flen: i64,
+}
+
+/// `(size, modified, content_hash)` for a previously indexed path, used to
+/// decide whether a file can skip re-hashing on this scan.
+pub(crate) struct PriorRow {
+    pub(crate) size: i64,
+    pub(crate) modified: TruncatedTimestamp,
+    pub(crate) content_hash: String,
 }
 
 fn create_database(connection: &Connection) -> Result<()> {
@@ -32,10 +51,13 @@ fn create_database(connection: &Connection) -> Result<()> {
     connection.execute(
         "CREATE TABLE IF NOT EXISTS files (
             hash TEXT NOT NULL,
+            content_hash TEXT NOT NULL DEFAULT '',
             path TEXT NOT NULL,
             size INTEGER NOT NULL,
             created INTEGER NOT NULL,
             modified INTEGER NOT NULL,
+            modified_nanos INTEGER NOT NULL DEFAULT 0,
+            modified_ambiguous INTEGER NOT NULL DEFAULT 0,
             plen INTEGER NOT NULL,
             flen INTEGER NOT NULL,
             timestamp INTEGER NOT NULL,
@@ -46,6 +68,42 @@ fn create_database(connection: &Connection) -> Result<()> {
         (),
     )?;
 
+    migrate_columns(connection)?;
+
+    Ok(())
+}
+
+/// `CREATE TABLE IF NOT EXISTS` only creates the current schema for a brand
+/// new `files.db`; an existing one from before `content_hash`/
+/// `modified_nanos`/`modified_ambiguous` were added keeps its old columns,
+/// and the first `INSERT`/`UPDATE` against it dies with "no such column".
+/// `ALTER TABLE ... ADD COLUMN` has no `IF NOT EXISTS`, so check
+/// `PRAGMA table_info` first and only add what's actually missing.
+fn migrate_columns(connection: &Connection) -> Result<()> {
+    let mut existing = std::collections::HashSet::new();
+    let mut stmt = connection.prepare("PRAGMA table_info(files)")?;
+    let mut rows = stmt.query([])?;
+    while let Some(row) = rows.next()? {
+        existing.insert(row.get::<_, String>(1)?);
+    }
+    drop(rows);
+    drop(stmt);
+
+    let added_columns = [
+        ("content_hash", "TEXT NOT NULL DEFAULT ''"),
+        ("modified_nanos", "INTEGER NOT NULL DEFAULT 0"),
+        ("modified_ambiguous", "INTEGER NOT NULL DEFAULT 0"),
+    ];
+
+    for (column, definition) in added_columns {
+        if !existing.contains(column) {
+            connection.execute(
+                &format!("ALTER TABLE files ADD COLUMN {column} {definition}"),
+                (),
+            )?;
+        }
+    }
+
     Ok(())
 }
 
@@ -53,6 +111,12 @@ fn create_index(connection: &Connection) {
     connection
         .execute("CREATE INDEX IF NOT EXISTS idx_hash ON files(hash)", ())
         .expect("INDEX ERROR ON HASH");
+    connection
+        .execute(
+            "CREATE INDEX IF NOT EXISTS idx_content_hash ON files(content_hash)",
+            (),
+        )
+        .expect("INDEX ERROR ON CONTENT_HASH");
     connection
         .execute("CREATE INDEX IF NOT EXISTS idx_path ON files(path)", ())
         .expect("INDEX ERROR ON PATH");
@@ -82,13 +146,66 @@ fn create_file(path: &str) -> std::io::Result<BufWriter<File>> {
     Ok(BufWriter::with_capacity(32 * 1024 * 1024, file))
 }
 
+/// Streams `path` through `xxh3` in fixed-size chunks so we never hold a
+/// whole large file in memory just to fingerprint its contents.
+fn hash_file_contents(path: &Path) -> std::io::Result<String> {
+    const CHUNK: usize = 1024 * 1024;
+
+    let file = File::open(path)?;
+    let mut reader = BufReader::with_capacity(CHUNK, file);
+    let mut hasher = Xxh3::new();
+    let mut buf = vec![0u8; CHUNK];
+
+    loop {
+        let read = reader.read(&mut buf)?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buf[..read]);
+    }
+
+    Ok(hasher.digest().to_string())
+}
+
+/// Loads the `(size, modified, content_hash)` of every currently indexed
+/// path so the walk can skip re-hashing files that haven't changed.
+fn load_prior_rows(connection: &Connection) -> Result<HashMap<String, PriorRow>> {
+    let mut stmt = connection
+        .prepare("SELECT path, size, modified, modified_nanos, modified_ambiguous, content_hash FROM files")?;
+    let mut rows = stmt.query([])?;
+
+    let mut map = HashMap::new();
+    while let Some(row) = rows.next()? {
+        let path: String = row.get(0)?;
+        map.insert(
+            path,
+            PriorRow {
+                size: row.get(1)?,
+                modified: TruncatedTimestamp {
+                    seconds: row.get(2)?,
+                    nanos: row.get(3)?,
+                    second_ambiguous: row.get(4)?,
+                },
+                content_hash: row.get(5)?,
+            },
+        );
+    }
+
+    Ok(map)
+}
+
 fn process_row(row: &Row) -> Datei {
     Datei {
         hash: row.get_unwrap::<_, String>("hash"),
+        content_hash: row.get_unwrap::<_, String>("content_hash"),
         path: row.get_unwrap::<_, String>("path"),
         size: row.get_unwrap::<_, i64>("size"),
         created: row.get_unwrap::<_, i64>("created"),
-        modified: row.get_unwrap::<_, i64>("modified"),
+        modified: TruncatedTimestamp {
+            seconds: row.get_unwrap::<_, i64>("modified"),
+            nanos: row.get_unwrap::<_, u32>("modified_nanos"),
+            second_ambiguous: row.get_unwrap::<_, bool>("modified_ambiguous"),
+        },
         plen: row.get_unwrap::<_, i64>("plen"),
         flen: row.get_unwrap::<_, i64>("flen"),
     }
@@ -110,7 +227,7 @@ fn build_table(mut rows: Rows) -> Result<Option<Table>> {
             .format("%d.%m.%Y %H:%M:%S")
             .to_string();
         let modified = Local
-            .timestamp_opt(datei.modified, 0)
+            .timestamp_opt(datei.modified.seconds, 0)
             .unwrap()
             .format("%d.%m.%Y %H:%M:%S")
             .to_string();
@@ -135,24 +252,65 @@ fn build_table(mut rows: Rows) -> Result<Option<Table>> {
     Ok(Some(table))
 }
 
-fn write_to_file(connection: &mut Connection, path: &str, timestamp: u64) -> Result<()> {
+/// Groups the index by `content_hash` and reports every set of files that
+/// share identical contents, along with the space that could be reclaimed
+/// by keeping only one copy of each set.
+fn build_duplicates_table(mut rows: Rows) -> Result<Option<(Table, i64)>> {
+    let mut table_builder = Builder::default();
+    table_builder.push_record(vec!["CONTENT_HASH", "SIZE", "COUNT", "RECLAIMABLE", "PATHS"]);
+
+    let mut found = false;
+    let mut reclaimable_total = 0i64;
+
+    while let Some(row) = rows.next()? {
+        let content_hash: String = row.get_unwrap(0);
+        let size: i64 = row.get_unwrap(1);
+        let count: i64 = row.get_unwrap(2);
+        let paths: String = row.get_unwrap(3);
+
+        found = true;
+        let reclaimable = size * (count - 1);
+        reclaimable_total += reclaimable;
+
+        table_builder.push_record(vec![
+            content_hash,
+            size.to_string(),
+            count.to_string(),
+            reclaimable.to_string(),
+            paths,
+        ]);
+    }
+
+    if !found {
+        return Ok(None);
+    }
+
+    let mut table = table_builder.build();
+    table.with(Style::psql());
+
+    Ok(Some((table, reclaimable_total)))
+}
+
+fn write_to_file(connection: &mut Connection, path: &str, timestamp: i64) -> Result<()> {
     let tx = connection.transaction()?;
 
     {
-        /*let mut sql_query_new_count = tx.prepare("SELECT COUNT(*) from files WHERE new = 1;")?;
-        let mut sql_query_modified_count =
-            tx.prepare("SELECT COUNT(*) FROM files WHERE timestamp = ?1 AND new = 0;")?;
-        let mut sql_query_deleted_count =
-            tx.prepare("SELECT COUNT(*) FROM files WHERE last_seen <> ?1")?;*/
-
         let mut sql_query_total_files_space =
             tx.prepare("SELECT round(SUM(size) / 1000000000.0, 2) FROM files;")?;
         let mut sql_query_total_files_count = tx.prepare("SELECT COUNT(*) FROM files;")?;
         let mut sql_query_new = tx.prepare(
-            "SELECT hash, path, size, created, modified, plen, flen FROM files WHERE new = 1;",
+            "SELECT hash, content_hash, path, size, created, modified, modified_nanos, modified_ambiguous, plen, flen FROM files WHERE new = 1;",
+        )?;
+        let mut sql_query_modified = tx.prepare("SELECT hash, content_hash, path, size, created, modified, modified_nanos, modified_ambiguous, plen, flen FROM files WHERE timestamp = ?1 AND new = 0;")?;
+        let mut sql_query_deleted = tx.prepare("SELECT hash, content_hash, path, size, created, modified, modified_nanos, modified_ambiguous, plen, flen FROM files WHERE last_seen <> ?1;")?;
+        let mut sql_query_duplicates = tx.prepare(
+            "SELECT content_hash, size, COUNT(*), group_concat(path, char(10))
+             FROM files
+             WHERE content_hash <> ''
+             GROUP BY content_hash, size
+             HAVING COUNT(*) > 1
+             ORDER BY size * (COUNT(*) - 1) DESC;",
         )?;
-        let mut sql_query_modified = tx.prepare("SELECT hash, path, size, created, modified, plen, flen FROM files WHERE timestamp = ?1 AND new = 0;")?;
-        let mut sql_query_deleted = tx.prepare("SELECT hash, path, size, created, modified, plen, flen FROM files WHERE last_seen <> ?1;")?;
 
         let mut file = create_file("output.txt").expect("Error creating file");
 
@@ -163,10 +321,12 @@ fn write_to_file(connection: &mut Connection, path: &str, timestamp: u64) -> Res
         let query_rows_new = sql_query_new.query([])?;
         let query_rows_modified = sql_query_modified.query([timestamp])?;
         let query_rows_deleted = sql_query_deleted.query([timestamp])?;
+        let query_rows_duplicates = sql_query_duplicates.query([])?;
 
         let table_new_files = build_table(query_rows_new)?;
         let table_modified_files = build_table(query_rows_modified)?;
         let table_deleted_files = build_table(query_rows_deleted)?;
+        let duplicates = build_duplicates_table(query_rows_duplicates)?;
 
         writeln!(
             file,
@@ -186,10 +346,19 @@ fn write_to_file(connection: &mut Connection, path: &str, timestamp: u64) -> Res
         }
 
         if let Some(table_deleted) = table_deleted_files {
-            writeln!(file, "Gelöschte Dateien:\n\n{}", table_deleted)
+            writeln!(file, "Gelöschte Dateien:\n\n{}\n\n", table_deleted)
                 .expect("Error while writing deleted files to file");
         }
 
+        if let Some((table_duplicates, reclaimable_total)) = duplicates {
+            writeln!(
+                file,
+                "Duplikate (reclaimable: {} bytes):\n\n{}",
+                reclaimable_total, table_duplicates
+            )
+            .expect("Error while writing duplicates to file");
+        }
+
         file.flush().expect("Writer flush error");
     }
     tx.commit()?;
@@ -197,73 +366,218 @@ fn write_to_file(connection: &mut Connection, path: &str, timestamp: u64) -> Res
     Ok(())
 }
 
-fn process_dir_entry(entry: &DirEntry<((), ())>) -> Result<Datei> {
-    let metadata = entry.metadata();
-    let path = entry.path();
+/// Builds the same new/modified/deleted report as `write_to_file`, but from
+/// an in-memory `snapshot::Diff` instead of a SQL query.
+fn snapshot_rows_table(rows: &[snapshot::Entry]) -> Option<Table> {
+    if rows.is_empty() {
+        return None;
+    }
 
-    let hash = xxh3_64(path.to_str().unwrap().as_bytes()).to_string();
+    let mut table_builder = Builder::default();
+    table_builder.push_record(vec!["SIZE", "CREATED", "MODIFIED", "PLEN", "FLEN", "PATH"]);
 
-    let size = match metadata {
-        Ok(ref data) => data.len(),
-        Err(_) => 0,
-    } as i64;
+    for entry in rows {
+        let created = Local
+            .timestamp_opt(entry.created as i64, 0)
+            .unwrap()
+            .format("%d.%m.%Y %H:%M:%S")
+            .to_string();
+        let modified = Local
+            .timestamp_opt(entry.modified.seconds, 0)
+            .unwrap()
+            .format("%d.%m.%Y %H:%M:%S")
+            .to_string();
 
-    let created = match metadata {
-        Ok(ref data) => match data.created() {
-            Ok(time) => time
-                .duration_since(std::time::UNIX_EPOCH)
-                .unwrap()
-                .as_secs(),
-            Err(_) => 0,
-        },
-        Err(_) => 0,
-    } as i64;
-
-    let modified = match metadata {
-        Ok(ref data) => match data.modified() {
-            Ok(time) => time
-                .duration_since(std::time::UNIX_EPOCH)
-                .unwrap()
-                .as_secs(),
-            Err(_) => 0,
+        table_builder.push_record(vec![
+            entry.size.to_string(),
+            created,
+            modified,
+            entry.plen.to_string(),
+            entry.flen.to_string(),
+            entry.path.clone(),
+        ]);
+    }
+
+    let mut table = table_builder.build();
+    table.with(Style::psql());
+    Some(table)
+}
+
+fn write_snapshot_report(path: &str, diff: &snapshot::Diff) -> std::io::Result<()> {
+    let mut file = create_file(path)?;
+
+    if let Some(table_new) = snapshot_rows_table(&diff.new) {
+        writeln!(file, "Neue Dateien:\n\n{}\n\n", table_new)?;
+    }
+
+    if let Some(table_modified) = snapshot_rows_table(&diff.modified) {
+        writeln!(file, "Geänderte Dateien:\n\n{}\n\n", table_modified)?;
+    }
+
+    if let Some(table_deleted) = snapshot_rows_table(&diff.deleted) {
+        writeln!(file, "Gelöschte Dateien:\n\n{}", table_deleted)?;
+    }
+
+    file.flush()
+}
+
+fn archive_rows_table(rows: &[archive::ArchivedFile]) -> Option<Table> {
+    if rows.is_empty() {
+        return None;
+    }
+
+    let mut table_builder = Builder::default();
+    table_builder.push_record(vec!["SIZE", "CREATED", "MODIFIED", "PLEN", "FLEN", "PATH"]);
+
+    for file in rows {
+        let created = Local
+            .timestamp_opt(file.created, 0)
+            .unwrap()
+            .format("%d.%m.%Y %H:%M:%S")
+            .to_string();
+        let modified = Local
+            .timestamp_opt(file.modified.seconds, 0)
+            .unwrap()
+            .format("%d.%m.%Y %H:%M:%S")
+            .to_string();
+
+        table_builder.push_record(vec![
+            file.size.to_string(),
+            created,
+            modified,
+            file.plen.to_string(),
+            file.flen.to_string(),
+            file.path.clone(),
+        ]);
+    }
+
+    let mut table = table_builder.build();
+    table.with(Style::psql());
+    Some(table)
+}
+
+fn write_archive_report(path: &str, diff: &archive::Diff) -> std::io::Result<()> {
+    let mut file = create_file(path)?;
+
+    if let Some(table_new) = archive_rows_table(&diff.new) {
+        writeln!(file, "Neue Dateien:\n\n{}\n\n", table_new)?;
+    }
+
+    if let Some(table_modified) = archive_rows_table(&diff.modified) {
+        writeln!(file, "Geänderte Dateien:\n\n{}\n\n", table_modified)?;
+    }
+
+    if let Some(table_deleted) = archive_rows_table(&diff.deleted) {
+        writeln!(file, "Gelöschte Dateien:\n\n{}", table_deleted)?;
+    }
+
+    file.flush()
+}
+
+const ROOT: &str = "/home/simon/";
+
+/// Which index backend to scan into. The SQLite backend is the historical,
+/// queryable default; the snapshot backend trades query flexibility for a
+/// read-mostly file that can be mmap'd and diffed without a SQL round-trip;
+/// the archive backend keeps every scan as its own immutable generation
+/// instead of overwriting the live table.
+#[allow(dead_code)]
+enum Backend {
+    Sqlite,
+    Snapshot,
+    Archive,
+}
+
+const BACKEND: Backend = Backend::Sqlite;
+
+/// Walks `ROOT` through `fs_source::scan`, reusing `prior_rows`' stored
+/// content hash for any file whose size and modified time haven't changed.
+/// This is the exact generic-over-`DirSource`/`Clocks` function
+/// `fs_source`'s own tests drive against a scripted virtual tree — wired
+/// here to the real filesystem via `RealDirSource` (which applies the
+/// ignore layer) and `FixedClock` (so the scan's "now" matches `scan_ts`,
+/// the same value the caller stamps every row with) — so the
+/// NEW/MODIFIED/DELETED classification downstream backends rely on can't
+/// silently diverge from what's tested. Shared by all three index backends
+/// so the walk/hash logic only lives once.
+fn scan_files(
+    ignore_layer: std::sync::Arc<IgnoreLayer>,
+    prior_rows: std::sync::Arc<HashMap<String, PriorRow>>,
+    scan_ts: TruncatedTimestamp,
+) -> Result<Vec<Datei>> {
+    let source = fs_source::RealDirSource::new(ignore_layer);
+    let clock = fs_source::FixedClock::new(scan_ts);
+
+    let entries = fs_source::scan(
+        &source,
+        &clock,
+        Path::new(ROOT),
+        &prior_rows,
+        direct_io::hash_file_contents,
+    );
+
+    Ok(entries
+        .into_iter()
+        .map(|entry| {
+            let hash = xxh3_64(entry.path.as_bytes()).to_string();
+            let flen = Path::new(&entry.path)
+                .file_name()
+                .map(|name| name.len())
+                .unwrap_or(0) as i64;
+
+            Datei {
+                hash,
+                content_hash: entry.content_hash,
+                plen: entry.path.len() as i64,
+                flen,
+                path: entry.path,
+                size: entry.size as i64,
+                created: entry.created as i64,
+                modified: entry.modified,
+            }
+        })
+        .collect())
+}
+
+fn new_ignore_layer() -> IgnoreLayer {
+    IgnoreLayer::new(
+        Path::new(ROOT),
+        &IgnoreConfig {
+            overrides: vec!["*.tmp".to_string()],
+            global_ignore: true,
         },
-        Err(_) => 0,
-    } as i64;
-
-    let plen = path.to_str().unwrap().len() as i64;
-    let flen = entry.file_name.len() as i64;
-
-    Ok(Datei {
-        hash: hash,
-        path: path.to_str().unwrap().to_string(),
-        size: size,
-        created: created,
-        modified: modified,
-        plen: plen,
-        flen: flen,
-    })
+    )
 }
 
-fn main() -> Result<()> {
+fn run_sqlite_backend() -> Result<()> {
     let mut db = Connection::open("files.db")?;
     create_database(&db)?;
 
     {
+        let prior_rows = std::sync::Arc::new(load_prior_rows(&db)?);
+        let ignore_layer = std::sync::Arc::new(new_ignore_layer());
+
         let tx = db.transaction()?;
 
         let mut insert = tx.prepare_cached(
-            "INSERT INTO files (hash, path, size, created, modified, plen, flen, timestamp, last_seen, new) 
-            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10) 
+            "INSERT INTO files (hash, content_hash, path, size, created, modified, modified_nanos, modified_ambiguous, plen, flen, timestamp, last_seen, new)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13)
             ON CONFLICT(hash, path) DO UPDATE SET
+                content_hash = excluded.content_hash,
                 size = excluded.size,
                 created = excluded.created,
                 modified = excluded.modified,
+                modified_nanos = excluded.modified_nanos,
+                modified_ambiguous = excluded.modified_ambiguous,
                 plen = excluded.plen,
                 flen = excluded.flen,
                 last_seen = excluded.last_seen,
                 timestamp = CASE
-                                WHEN size <> excluded.size 
+                                WHEN size <> excluded.size
                                 OR modified <> excluded.modified
+                                OR modified_nanos <> excluded.modified_nanos
+                                OR excluded.modified_ambiguous = 1
+                                OR modified_ambiguous = 1
                                 THEN excluded.timestamp
                                 ELSE timestamp
                             END,
@@ -272,35 +586,25 @@ fn main() -> Result<()> {
 
         tx.execute("UPDATE files SET new = 0", ())?;
 
-        let timestamp = std::time::SystemTime::now()
-            .duration_since(std::time::UNIX_EPOCH)
-            .unwrap()
-            .as_secs();
-
-        for dir_entry in WalkDir::new("/home/simon/") {
-            match dir_entry {
-                Ok(entry) => {
-                    if !entry.file_type().is_file() {
-                        continue;
-                    }
-
-                    let datei = process_dir_entry(&entry)?;
-
-                    insert.execute(params![
-                        datei.hash,
-                        datei.path,
-                        datei.size,
-                        datei.created,
-                        datei.modified,
-                        datei.plen,
-                        datei.flen,
-                        timestamp,
-                        timestamp,
-                        1
-                    ])?;
-                }
-                Err(_) => (),
-            };
+        let scan_ts = TruncatedTimestamp::now();
+        let timestamp = scan_ts.seconds;
+
+        for datei in scan_files(ignore_layer, prior_rows, scan_ts)? {
+            insert.execute(params![
+                datei.hash,
+                datei.content_hash,
+                datei.path,
+                datei.size,
+                datei.created,
+                datei.modified.seconds,
+                datei.modified.nanos,
+                datei.modified.second_ambiguous,
+                datei.plen,
+                datei.flen,
+                timestamp,
+                timestamp,
+                1
+            ])?;
         }
 
         drop(insert);
@@ -315,3 +619,65 @@ fn main() -> Result<()> {
 
     Ok(())
 }
+
+const SNAPSHOT_PATH: &str = "files.snapshot";
+
+fn run_snapshot_backend() -> std::io::Result<()> {
+    let ignore_layer = std::sync::Arc::new(new_ignore_layer());
+    let prior_snapshot = snapshot::load_snapshot(Path::new(SNAPSHOT_PATH)).ok();
+
+    // The snapshot format stores no content hash, so there is nothing to
+    // reuse one from and every file gets re-hashed.
+    let prior_rows = std::sync::Arc::new(HashMap::new());
+    let scan_ts = TruncatedTimestamp::now();
+
+    let entries = scan_files(ignore_layer, prior_rows, scan_ts).expect("scan failed");
+    let current: Vec<snapshot::Entry> = entries
+        .iter()
+        .map(|datei| snapshot::Entry {
+            path: datei.path.clone(),
+            size: datei.size as u64,
+            created: datei.created as u64,
+            modified: datei.modified,
+            plen: datei.plen as u32,
+            flen: datei.flen as u32,
+            state: snapshot::EntryState::SEEN,
+        })
+        .collect();
+
+    let diff = snapshot::diff_against(&current, prior_snapshot.as_ref());
+    write_snapshot_report("output.txt", &diff)?;
+
+    snapshot::save_snapshot(Path::new(SNAPSHOT_PATH), &current)
+}
+
+fn run_archive_backend() -> Result<()> {
+    let mut db = Connection::open("files.db")?;
+    archive::create_archive_tables(&db)?;
+
+    let ignore_layer = std::sync::Arc::new(new_ignore_layer());
+    // The archive's events do hold each path's content hash, size and
+    // modified time, but they are not loaded into this reuse map, so every
+    // file is re-hashed each run.
+    let prior_rows = std::sync::Arc::new(HashMap::new());
+    let scan_ts = TruncatedTimestamp::now();
+
+    let entries = scan_files(ignore_layer, prior_rows, scan_ts)?;
+    let diff = archive::record_scan(&mut db, ROOT, &entries, scan_ts.seconds)?;
+
+    write_archive_report("output.txt", &diff)
+        .expect("Error while writing archive report to file");
+
+    Ok(())
+}
+
+fn main() -> Result<()> {
+    match BACKEND {
+        Backend::Sqlite => run_sqlite_backend(),
+        Backend::Archive => run_archive_backend(),
+        Backend::Snapshot => {
+            run_snapshot_backend().expect("snapshot backend failed");
+            Ok(())
+        }
+    }
+}
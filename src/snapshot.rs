@@ -0,0 +1,233 @@
+//! A compact, memory-mappable binary snapshot format, used as a lighter
+//! alternative to the SQLite index for read-mostly scans. Modeled on
+//! Mercurial's dirstate-v2: a small header, then a flat array of
+//! fixed-width records (pointing into a trailing string table for paths),
+//! so a previous snapshot can be mmap'd and reinterpreted in place instead
+//! of being parsed row by row.
+
+use crate::timestamp::TruncatedTimestamp;
+use bitflags::bitflags;
+use memmap2::Mmap;
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{self, BufWriter, Write},
+    mem::size_of,
+    path::Path,
+};
+
+const MAGIC: [u8; 4] = *b"FWS1";
+const FORMAT_VERSION: u32 = 1;
+
+bitflags! {
+    /// Per-entry lifecycle state, written alongside the rest of the record.
+    /// The byte holds whatever the caller of `save_snapshot` set; the scan in
+    /// `main` saves every entry as `SEEN`, so new/modified/deleted entries
+    /// are told apart by `diff_against`, not by this byte.
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    pub struct EntryState: u8 {
+        const NEW      = 0b0001;
+        const MODIFIED = 0b0010;
+        const SEEN      = 0b0100;
+        const DELETED  = 0b1000;
+    }
+}
+
+#[repr(C, packed)]
+#[derive(Clone, Copy)]
+struct Header {
+    magic: [u8; 4],
+    format_version: u32,
+    entry_count: u64,
+}
+
+/// Fixed-width on-disk record. `repr(C, packed)` gives it alignment 1, so a
+/// byte slice read straight from an mmap can be reinterpreted as `&[RawEntry]`
+/// with no copying and no alignment requirement on the backing buffer.
+#[repr(C, packed)]
+#[derive(Clone, Copy)]
+struct RawEntry {
+    path_offset: u32,
+    path_len: u32,
+    size: u64,
+    created: u64,
+    modified_seconds: i64,
+    modified_nanos: u32,
+    modified_ambiguous: u8,
+    plen: u32,
+    flen: u32,
+    state: u8,
+    _reserved: [u8; 2],
+}
+
+/// One entry as handed to `save_snapshot`, or read back out of a loaded one.
+#[derive(Clone, Debug)]
+pub struct Entry {
+    pub path: String,
+    pub size: u64,
+    pub created: u64,
+    pub modified: TruncatedTimestamp,
+    pub plen: u32,
+    pub flen: u32,
+    pub state: EntryState,
+}
+
+/// Reinterprets `bytes` as a `&[T]` with no copy. Sound only for `T` whose
+/// required alignment is 1 (i.e. `repr(C, packed)`), which is what every
+/// caller in this module passes.
+unsafe fn cast_slice<T>(bytes: &[u8]) -> &[T] {
+    unsafe {
+        let len = bytes.len() / size_of::<T>();
+        std::slice::from_raw_parts(bytes.as_ptr() as *const T, len)
+    }
+}
+
+/// Reinterprets a single `&T` as its raw bytes for writing. Sound under the
+/// same `repr(C, packed)` (alignment 1) requirement as `cast_slice`.
+unsafe fn struct_bytes<T>(value: &T) -> &[u8] {
+    unsafe { std::slice::from_raw_parts((value as *const T) as *const u8, size_of::<T>()) }
+}
+
+pub fn save_snapshot(path: &Path, entries: &[Entry]) -> io::Result<()> {
+    let mut string_table = Vec::new();
+    let mut raw_entries = Vec::with_capacity(entries.len());
+
+    for entry in entries {
+        let path_offset = string_table.len() as u32;
+        string_table.extend_from_slice(entry.path.as_bytes());
+
+        raw_entries.push(RawEntry {
+            path_offset,
+            path_len: entry.path.len() as u32,
+            size: entry.size,
+            created: entry.created,
+            modified_seconds: entry.modified.seconds,
+            modified_nanos: entry.modified.nanos,
+            modified_ambiguous: entry.modified.second_ambiguous as u8,
+            plen: entry.plen,
+            flen: entry.flen,
+            state: entry.state.bits(),
+            _reserved: [0; 2],
+        });
+    }
+
+    let header = Header {
+        magic: MAGIC,
+        format_version: FORMAT_VERSION,
+        entry_count: raw_entries.len() as u64,
+    };
+
+    let file = File::create(path)?;
+    let mut writer = BufWriter::new(file);
+    writer.write_all(unsafe { struct_bytes(&header) })?;
+    writer.write_all(unsafe {
+        std::slice::from_raw_parts(
+            raw_entries.as_ptr() as *const u8,
+            raw_entries.len() * size_of::<RawEntry>(),
+        )
+    })?;
+    writer.write_all(&string_table)?;
+    writer.flush()
+}
+
+/// A loaded snapshot, still backed by its mmap. Loading maps the file
+/// without reading its records; `entries()` reinterprets the record array in
+/// place with a pointer cast, then decodes every record (copying its path out
+/// of the string table) into an owned `Entry`, so it is O(n) in the entry
+/// count.
+pub struct Snapshot {
+    mmap: Mmap,
+}
+
+impl Snapshot {
+    pub fn entries(&self) -> Vec<Entry> {
+        let header_size = size_of::<Header>();
+        let header: &Header = unsafe { &*(self.mmap.as_ptr() as *const Header) };
+        if header.magic != MAGIC {
+            return Vec::new();
+        }
+
+        let entry_count = header.entry_count as usize;
+        let raw_bytes_len = entry_count * size_of::<RawEntry>();
+        let raw_bytes = &self.mmap[header_size..header_size + raw_bytes_len];
+        let raw_entries: &[RawEntry] = unsafe { cast_slice(raw_bytes) };
+        let string_table = &self.mmap[header_size + raw_bytes_len..];
+
+        raw_entries
+            .iter()
+            .map(|raw| {
+                let start = raw.path_offset as usize;
+                let end = start + raw.path_len as usize;
+                let path = std::str::from_utf8(&string_table[start..end])
+                    .unwrap_or_default()
+                    .to_string();
+                Entry {
+                    path,
+                    size: raw.size,
+                    created: raw.created,
+                    modified: TruncatedTimestamp {
+                        seconds: raw.modified_seconds,
+                        nanos: raw.modified_nanos,
+                        // Kept as stored: an mtime that shared its second
+                        // with the scan that saved it can't rule out a later
+                        // write in that same second, so `diff_against`
+                        // treats the entry as possibly changed.
+                        second_ambiguous: raw.modified_ambiguous != 0,
+                    },
+                    plen: raw.plen,
+                    flen: raw.flen,
+                    state: EntryState::from_bits_truncate(raw.state),
+                }
+            })
+            .collect()
+    }
+}
+
+pub fn load_snapshot(path: &Path) -> io::Result<Snapshot> {
+    let file = File::open(path)?;
+    let mmap = unsafe { Mmap::map(&file)? };
+    Ok(Snapshot { mmap })
+}
+
+/// The same new/modified/deleted classification the SQLite backend computes
+/// with `WHERE new = 1` / `timestamp = ?` / `last_seen <> ?`, but diffed
+/// in memory against a loaded snapshot instead of round-tripping through SQL.
+pub struct Diff {
+    pub new: Vec<Entry>,
+    pub modified: Vec<Entry>,
+    pub deleted: Vec<Entry>,
+}
+
+pub fn diff_against(current: &[Entry], prior: Option<&Snapshot>) -> Diff {
+    let prior_entries = prior.map(Snapshot::entries).unwrap_or_default();
+    let prior_by_path: HashMap<&str, &Entry> = prior_entries
+        .iter()
+        .map(|entry| (entry.path.as_str(), entry))
+        .collect();
+
+    let mut new = Vec::new();
+    let mut modified = Vec::new();
+    let mut current_paths = std::collections::HashSet::with_capacity(current.len());
+    for entry in current {
+        current_paths.insert(entry.path.as_str());
+        match prior_by_path.get(entry.path.as_str()) {
+            None => new.push(entry.clone()),
+            Some(p) => {
+                if p.size != entry.size || p.modified.maybe_changed(&entry.modified) {
+                    modified.push(entry.clone());
+                }
+            }
+        }
+    }
+
+    let deleted = prior_entries
+        .into_iter()
+        .filter(|p| !current_paths.contains(p.path.as_str()))
+        .collect();
+
+    Diff {
+        new,
+        modified,
+        deleted,
+    }
+}
@@ -0,0 +1,48 @@
+//! Modification-time tracking at nanosecond resolution, borrowed from
+//! Mercurial's dirstate: a plain truncated-to-seconds timestamp can't tell a
+//! sub-second edit from "unchanged", and a file saved in the same wall-clock
+//! second as the scan itself can't be trusted either way, so we flag that
+//! case explicitly instead of silently trusting it.
+
+use std::time::SystemTime;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TruncatedTimestamp {
+    pub seconds: i64,
+    pub nanos: u32,
+    /// Set when `seconds` falls in the same wall-clock second as the scan
+    /// that observed it — filesystem mtime granularity means a later write
+    /// within that same second could be invisible to a plain comparison.
+    pub second_ambiguous: bool,
+}
+
+impl TruncatedTimestamp {
+    /// Builds a timestamp for `time`, flagging it ambiguous if it lands in
+    /// the same second as `scan_seconds` (the scan's own "now").
+    pub fn from_system_time(time: SystemTime, scan_seconds: i64) -> Self {
+        let duration = time
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap_or_default();
+        let seconds = duration.as_secs() as i64;
+        TruncatedTimestamp {
+            seconds,
+            nanos: duration.subsec_nanos(),
+            second_ambiguous: seconds == scan_seconds,
+        }
+    }
+
+    /// A timestamp for "now", used as the scan's own reference point.
+    pub fn now() -> Self {
+        Self::from_system_time(SystemTime::now(), i64::MIN)
+    }
+
+    /// True if `self` and `other` should be treated as possibly-dirty: they
+    /// differ outright, or either was observed in the same second as its
+    /// scan and so can't be trusted to rule out a change.
+    pub fn maybe_changed(&self, other: &TruncatedTimestamp) -> bool {
+        self.seconds != other.seconds
+            || self.nanos != other.nanos
+            || self.second_ambiguous
+            || other.second_ambiguous
+    }
+}